@@ -1,8 +1,91 @@
-use std::fs::{read_dir, Metadata};
-use std::io::{self, Read, Write};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use std::str;
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+#[cfg(feature = "secure")]
+use std::pin::Pin;
+#[cfg(feature = "secure")]
+use std::task::{Context, Poll};
+#[cfg(feature = "secure")]
+use tokio::io::{AsyncRead, ReadBuf};
+
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "secure")]
+use tokio_native_tls::{TlsAcceptor, TlsStream};
+
+// Dengan fitur `secure` dinyalakan, baik control connection maupun data connection
+// bisa berupa socket TLS setelah AUTH TLS/PROT P, jadi keduanya dibungkus di balik
+// enum yang sama supaya send_cmd/send_data tetap bekerja tanpa peduli jenis socketnya.
+#[cfg(feature = "secure")]
+enum Stream {
+    Plain(TcpStream),
+    Secure(Box<TlsStream<TcpStream>>),
+}
+
+#[cfg(not(feature = "secure"))]
+type Stream = TcpStream;
+
+#[cfg(feature = "secure")]
+fn wrap_plain(stream: TcpStream) -> Stream {
+    Stream::Plain(stream)
+}
+
+#[cfg(not(feature = "secure"))]
+fn wrap_plain(stream: TcpStream) -> Stream {
+    stream
+}
+
+#[cfg(feature = "secure")]
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            Stream::Secure(s) => Pin::new(s.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(feature = "secure")]
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            Stream::Secure(s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(s) => Pin::new(s).poll_flush(cx),
+            Stream::Secure(s) => Pin::new(s.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            Stream::Secure(s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 #[repr(u32)]
@@ -25,6 +108,7 @@ enum ResultCode {
     ClosingDataConnection = 226,
     EnteringPassiveMode = 227,
     UserLoggedIn = 230,
+    AuthOkayNoDataNeeded = 234,
     RequestedFileActionOkay = 250,
     PATHNAMECreated = 257,
     UserNameOkayNeedPassword = 331,
@@ -50,17 +134,32 @@ enum ResultCode {
 }
 
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 enum Command {
     Auth,
     List,
-    // Cwd(PathBuf),
+    Cwd(PathBuf),
+    CdUp,
+    Mkd(PathBuf),
+    Rmd(PathBuf),
+    Dele(PathBuf),
+    Rnfr(PathBuf),
+    Rnto(PathBuf),
     Syst, //implemantation command
     NoOp,
     Pwd,
+    Retr(PathBuf), //download file dari server ke client
+    Stor(PathBuf), //upload file dari client ke server
+    Size(PathBuf), //ukuran file dalam bytes
+    Mdtm(PathBuf), //waktu modifikasi file terakhir
     Type, //Anda dapat mentransfer data dengan extensi yang berbeda.
     Pasv,
+    Port(Ipv4Addr, u16), //active mode: klien yang memberitahu alamat untuk kita hubungi
+    Pbsz, //protection buffer size, selalu 0 untuk TLS
+    Prot(char), //'P' = private (data connection dienkripsi), 'C' = clear
     Unknown(String), //Jika perintah tersebut tidak ada (atau kita belum mengimplementasikannya belum diimplementasikan), Unknown akan dikembalikan dengan nama perintah.
     User(String),
+    Pass(String),
 }
 
 // Dalam contoh ini, as_ref digunakan untuk mendapatkan referensi ke string yang sesuai dengan masing-masing varian enum.
@@ -70,14 +169,28 @@ impl AsRef<str> for Command {
         match *self {
             Command::Auth => "AUTH",
             Command::List => "LIST",
-            // Command::Cwd(_) => "CWD",
+            Command::Cwd(_) => "CWD",
+            Command::CdUp => "CDUP",
+            Command::Mkd(_) => "MKD",
+            Command::Rmd(_) => "RMD",
+            Command::Dele(_) => "DELE",
+            Command::Rnfr(_) => "RNFR",
+            Command::Rnto(_) => "RNTO",
             Command::Syst => "SYST",
             Command::NoOp => "NOOP",
             Command::Pwd => "PWD",
+            Command::Retr(_) => "RETR",
+            Command::Stor(_) => "STOR",
+            Command::Size(_) => "SIZE",
+            Command::Mdtm(_) => "MDTM",
             Command::Type => "TYPE",
             Command::Pasv => "PASV",
+            Command::Port(..) => "PORT",
+            Command::Pbsz => "PBSZ",
+            Command::Prot(_) => "PROT",
             Command::Unknown(_) => "UNKW",
             Command::User(_) => "USER",
+            Command::Pass(_) => "PASS",
         }
     }
 }
@@ -96,13 +209,73 @@ impl Command {
         let data = iter.next();
         let command = match command.as_slice() {
             b"AUTH" => Command::Auth,
+            b"LIST" => Command::List,
+            b"NOOP" => Command::NoOp,
+            b"PWD" => Command::Pwd,
+            b"TYPE" => Command::Type,
+            b"PASV" => Command::Pasv,
             b"SYST" => Command::Syst,
+            b"RETR" => Command::Retr(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"STOR" => Command::Stor(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"SIZE" => Command::Size(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"MDTM" => Command::Mdtm(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"CWD" => Command::Cwd(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"CDUP" => Command::CdUp,
+            b"MKD" => Command::Mkd(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"RMD" => Command::Rmd(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"DELE" => Command::Dele(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"RNFR" => Command::Rnfr(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"RNTO" => Command::Rnto(
+                data.map(|bytes| PathBuf::from(str::from_utf8(bytes).unwrap_or("")))
+                    .unwrap_or_default(),
+            ),
+            b"PORT" => {
+                let addr = data.and_then(|bytes| str::from_utf8(bytes).ok());
+                match addr.and_then(parse_port_arg) {
+                    Some((ip, port)) => Command::Port(ip, port),
+                    None => Command::Unknown("PORT".to_owned()),
+                }
+            }
+            b"PBSZ" => Command::Pbsz,
+            b"PROT" => Command::Prot(
+                data.and_then(|bytes| bytes.first())
+                    .map(|&b| b as char)
+                    .unwrap_or('C'),
+            ),
             b"USER" => Command::User(
-                data.map(|bytes| {
-                    String::from_utf8(bytes.to_vec()).expect("cannot convert bytes to string")
-                })
-                .unwrap_or_default()
-                .to_owned(),
+                data.map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .unwrap_or_default(),
+            ),
+            b"PASS" => Command::Pass(
+                data.map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+                    .unwrap_or_default(),
             ),
             s => Command::Unknown(str::from_utf8(s).unwrap_or("").to_owned()),
         };
@@ -112,185 +285,793 @@ impl Command {
 
 fn to_uppercase(data: &mut [u8]) {
     for byte in data {
-        if *byte >= 'a' as u8 && *byte <= 'z' as u8 {
+        if *byte >= b'a' && *byte <= b'z' {
             *byte -= 32;
         }
     }
 }
 
-// Sekarang kita dapat menulis fungsi untuk membaca data dari klien:
-fn read_all_message(stream: &mut TcpStream) -> Vec<u8> {
-    let buf = &mut [0; 1];
-    let mut out = Vec::with_capacity(100);
+// Parses the `h1,h2,h3,h4,p1,p2` argument PORT sends, the inverse of the
+// `(h1,h2,h3,h4,p1,p2)` tuple found in a PASV reply: port = p1*256 + p2.
+fn parse_port_arg(arg: &str) -> Option<(Ipv4Addr, u16)> {
+    let parts: Vec<&str> = arg.trim().split(',').collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    let mut nums = [0u16; 6];
+    for (i, part) in parts.iter().enumerate() {
+        nums[i] = part.parse().ok()?;
+    }
+    let ip = Ipv4Addr::new(nums[0] as u8, nums[1] as u8, nums[2] as u8, nums[3] as u8);
+    let port = nums[4] * 256 + nums[5];
+    Some((ip, port))
+}
 
-    // infinity loop
-    loop {
-        match stream.read(buf) {
-            Ok(received) if received > 0 => {
-                if out.is_empty() && buf[0] == b' ' {
-                    continue;
-                }
-                out.push(buf[0])
-            }
-            _ => return Vec::new(),
-        }
+#[derive(Clone, Debug)]
+#[allow(dead_code)]
+enum LoginState {
+    Anonymous,
+    WaitingForPassword(String),
+    LoggedIn(String),
+}
 
-        let len = out.len();
-        if len > 1 && out[len - 2] == b'\r' && out[len - 1] == b'\n' {
-            out.pop();
-            out.pop();
-            return out;
-        }
+// Garam acak 16-byte per kredensial; dibuat sekali saat startup di `load_credentials`.
+fn generate_salt() -> String {
+    let mut rng = rand::thread_rng();
+    (0..16).map(|_| format!("{:02x}", rng.gen::<u8>())).collect()
+}
+
+// SHA-256 dari salt+password, bukan DefaultHasher (SipHash) - itu untuk bucketing HashMap,
+// bukan penyimpanan kredensial, dan bisa di-brute-force offline dengan mudah.
+fn hash_password(password: &str, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+// Daftar kredensial di-hardcode dan dimuat sekali saat startup; di produksi ini akan
+// dibaca dari sebuah file konfigurasi. Setiap entri menyimpan (salt, hash) alih-alih
+// password mentah atau hash tak bergaram.
+fn load_credentials() -> HashMap<String, (String, String)> {
+    let mut credentials = HashMap::new();
+    for (user, password) in [("anonymous", ""), ("admin", "admin")] {
+        let salt = generate_salt();
+        let hash = hash_password(password, &salt);
+        credentials.insert(user.to_owned(), (salt, hash));
     }
+    credentials
 }
 
 #[allow(dead_code)]
 struct Client {
     cwd: PathBuf, //adalah singkatan dari direktori kerja saat ini stream adalah soket klien
-    stream: TcpStream, // socket client
-    name: Option<String>, //pengguna yang Anda dapatkan dari autentikasi pengguna
-    data_writer: Option<TcpStream>,
+    // Batas sandbox untuk navigasi/mutasi filesystem; tidak ada path client yang boleh
+    // resolve ke luar direktori ini (lihat `resolve_path`).
+    root: PathBuf,
+    // Dibungkus Option supaya AUTH TLS bisa `take()` control stream yang lama, mengupgrade-nya,
+    // lalu mengembalikannya tanpa harus "mencabut" TcpStream dari dalam referensi bersama.
+    stream: Option<BufReader<Stream>>,
+    login: LoginState, //status autentikasi: belum login, menunggu PASS, atau sudah login
+    credentials: Arc<HashMap<String, (String, String)>>,
+    data_writer: Option<Stream>,
+    port_addr: Option<SocketAddr>, // alamat client setelah PORT, dipakai untuk active mode
+    // IP peer control connection; dipakai untuk menolak PORT yang menunjuk host lain
+    // (classic FTP bounce attack) alih-alih membiarkan server dial sembarang alamat.
+    control_peer_ip: Option<IpAddr>,
+    rename_from: Option<PathBuf>, // path sumber yang ditunggu RNTO setelah RNFR
+    #[cfg(feature = "secure")]
+    tls_acceptor: Option<Arc<TlsAcceptor>>, // diset oleh server supaya AUTH TLS bisa upgrade koneksi
+    #[cfg(feature = "secure")]
+    protected: bool, // true setelah PROT P, artinya data connection juga harus TLS
 }
 
 impl Client {
-    fn new(stream: TcpStream) -> Client {
+    fn new(stream: TcpStream, credentials: Arc<HashMap<String, (String, String)>>, root: PathBuf) -> Client {
+        let control_peer_ip = stream.peer_addr().ok().map(|addr| addr.ip());
         Client {
-            cwd: PathBuf::from("/"), // root dir
-            stream: stream,
-            name: None,
+            cwd: root.clone(), // client mulai tepat di server root
+            root,
+            stream: Some(BufReader::new(wrap_plain(stream))),
+            login: LoginState::Anonymous,
+            credentials,
             data_writer: None,
+            port_addr: None,
+            control_peer_ip,
+            rename_from: None,
+            #[cfg(feature = "secure")]
+            tls_acceptor: None,
+            #[cfg(feature = "secure")]
+            protected: false,
+        }
+    }
+
+    // Gabungkan `path` ke cwd lalu kanonikalisasi, dan tolak hasil yang kabur keluar
+    // dari `self.root` (mis. lewat `..`) dengan mengembalikan `None`.
+    fn resolve_path(&self, path: &std::path::Path) -> Option<PathBuf> {
+        let root = std::fs::canonicalize(&self.root).ok()?;
+        let candidate = self.cwd.join(path);
+        let canonical = match std::fs::canonicalize(&candidate) {
+            Ok(canonical) => canonical,
+            // Belum ada di filesystem (mis. target MKD/RNTO) - kanonikalisasi parent-nya saja.
+            Err(_) => {
+                let parent = std::fs::canonicalize(candidate.parent()?).ok()?;
+                parent.join(candidate.file_name()?)
+            }
+        };
+        if canonical.starts_with(&root) {
+            Some(canonical)
+        } else {
+            None
+        }
+    }
+
+    // Mengubah path absolut di filesystem asli jadi path virtual relatif terhadap self.root,
+    // supaya balasan ke client (PWD, MKD, ...) tidak pernah membocorkan path asli di server.
+    fn virtual_path(&self, real: &std::path::Path) -> String {
+        let root = std::fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let relative = real
+            .strip_prefix(&root)
+            .or_else(|_| real.strip_prefix(&self.root))
+            .unwrap_or(real);
+        format!("/{}", relative.to_str().unwrap_or(""))
+    }
+
+    #[cfg(feature = "secure")]
+    fn with_tls_acceptor(
+        stream: TcpStream,
+        credentials: Arc<HashMap<String, (String, String)>>,
+        root: PathBuf,
+        acceptor: Arc<TlsAcceptor>,
+    ) -> Client {
+        let mut client = Client::new(stream, credentials, root);
+        client.tls_acceptor = Some(acceptor);
+        client
+    }
+
+    fn is_logged_in(&self) -> bool {
+        matches!(self.login, LoginState::LoggedIn(_))
+    }
+
+    // Dipanggil di awal setiap perintah yang butuh sesi penuh (LIST/RETR/STOR/PWD/PASV,
+    // CWD/CDUP/MKD/RMD/DELE/RNFR/RNTO, SIZE/MDTM);
+    // mengirim 530 dan memberi tahu pemanggil untuk berhenti kalau belum login.
+    async fn require_login(&mut self) -> bool {
+        if self.is_logged_in() {
+            true
+        } else {
+            self.reply(ResultCode::NotLoggedIn, "Please login with USER and PASS")
+                .await;
+            false
+        }
+    }
+
+    fn writer(&mut self) -> &mut Stream {
+        self.stream
+            .as_mut()
+            .expect("control stream always present outside of an in-flight AUTH TLS upgrade")
+            .get_mut()
+    }
+
+    async fn reply(&mut self, code: ResultCode, message: &str) {
+        send_cmd(self.writer(), code, message).await;
+    }
+
+    // Membaca satu baris perintah dari control connection, memotongnya di `\r\n`.
+    // Mengembalikan `None` ketika client menutup koneksi (EOF).
+    async fn read_command(&mut self) -> io::Result<Option<Vec<u8>>> {
+        // Stream kosong berarti handshake AUTH TLS gagal; sesi diakhiri seperti EOF.
+        let reader = match self.stream.as_mut() {
+            Some(reader) => reader,
+            None => return Ok(None),
+        };
+
+        let mut line = Vec::with_capacity(100);
+        let received = reader.read_until(b'\n', &mut line).await?;
+        if received == 0 {
+            return Ok(None);
+        }
+
+        while matches!(line.last(), Some(b'\n') | Some(b'\r')) {
+            line.pop();
+        }
+        while line.first() == Some(&b' ') {
+            line.remove(0);
+        }
+
+        Ok(Some(line))
+    }
+
+    // Menangani AUTH TLS: men-"take" control stream lama keluar dari BufReader, membungkusnya
+    // dengan TlsAcceptor, lalu menaruhnya kembali. Setiap byte yang sudah dibuffer tapi belum
+    // dibaca akan hilang, yang wajar karena client seharusnya diam menunggu balasan 234.
+    #[cfg(feature = "secure")]
+    async fn auth_tls(&mut self) {
+        let acceptor = match self.tls_acceptor.clone() {
+            Some(acceptor) => acceptor,
+            None => {
+                self.reply(
+                    ResultCode::CommandNotImplemented,
+                    "TLS is not configured on this server",
+                )
+                .await;
+                return;
+            }
+        };
+
+        let inner = self
+            .stream
+            .take()
+            .expect("control stream always present outside of an in-flight AUTH TLS upgrade")
+            .into_inner();
+
+        let mut plain = match inner {
+            Stream::Plain(s) => s,
+            Stream::Secure(s) => {
+                self.stream = Some(BufReader::new(Stream::Secure(s)));
+                self.reply(ResultCode::BadSequenceOfCommands, "already using TLS")
+                    .await;
+                return;
+            }
+        };
+
+        send_cmd(&mut plain, ResultCode::AuthOkayNoDataNeeded, "AUTH TLS successful").await;
+
+        match acceptor.accept(plain).await {
+            Ok(tls_stream) => {
+                self.stream = Some(BufReader::new(Stream::Secure(Box::new(tls_stream))));
+            }
+            Err(_) => {
+                // Handshake gagal setelah kita sudah bilang 234; control channel ini sudah
+                // tidak bisa dipakai lagi, jadi self.stream dibiarkan None dan sesi berakhir
+                // begitu handle_client mencoba membaca baris berikutnya.
+            }
+        }
+    }
+
+    // Membungkus socket data mentah sesuai PROT yang aktif: kalau `protected` (PROT P),
+    // handshake TLS dulu; kalau tidak, biarkan plaintext. Dipakai bersama oleh jalur PASV
+    // (`finish_pasv_connection`) dan jalur PORT (`acquire_data_connection`) supaya keduanya
+    // menghormati PROT P, bukan cuma PASV.
+    #[cfg(feature = "secure")]
+    async fn wrap_for_protection(&mut self, stream: TcpStream) -> Result<Stream, &'static str> {
+        if self.protected {
+            match self.tls_acceptor.clone() {
+                Some(acceptor) => acceptor
+                    .accept(stream)
+                    .await
+                    .map(|tls| Stream::Secure(Box::new(tls)))
+                    .map_err(|_| "TLS handshake on data connection failed"),
+                None => Err("TLS not configured"),
+            }
+        } else {
+            Ok(wrap_plain(stream))
         }
     }
 
-    fn handle_cmd(&mut self, cmd: Command) {
+    #[cfg(not(feature = "secure"))]
+    async fn wrap_for_protection(&mut self, stream: TcpStream) -> Result<Stream, &'static str> {
+        Ok(wrap_plain(stream))
+    }
+
+    // Menyatukan jalur PASV (accept dari listener ephemeral) dan jalur PORT (connect keluar)
+    // jadi satu data connection, supaya RETR/STOR/LIST tidak peduli mode mana yang dipakai client.
+    async fn acquire_data_connection(&mut self) -> Option<Stream> {
+        if let Some(stream) = self.data_writer.take() {
+            return Some(stream);
+        }
+
+        let addr = self.port_addr?;
+        let stream = TcpStream::connect(addr).await.ok()?;
+        self.wrap_for_protection(stream).await.ok()
+    }
+
+    async fn finish_pasv_connection(&mut self, client: TcpStream) {
+        match self.wrap_for_protection(client).await {
+            Ok(stream) => self.data_writer = Some(stream),
+            Err(message) => self.reply(ResultCode::CantOpenDataConnection, message).await,
+        }
+    }
+
+    async fn handle_cmd(&mut self, cmd: Command) {
         println!("========> {:?}", cmd);
         match cmd {
-            Command::Auth => send_cmd(
-                &mut self.stream,
-                ResultCode::CommandNotImplemented,
-                "Not Implemented",
-            ),
-            Command::NoOp => send_cmd(&mut self.stream, ResultCode::Ok, "Doing nothing..."),
+            #[cfg(not(feature = "secure"))]
+            Command::Auth => {
+                self.reply(ResultCode::CommandNotImplemented, "Not Implemented")
+                    .await
+            }
+            #[cfg(feature = "secure")]
+            Command::Auth => self.auth_tls().await,
 
-            Command::Syst => send_cmd(&mut self.stream, ResultCode::Ok, "I won't tell"),
+            #[cfg(feature = "secure")]
+            Command::Pbsz => self.reply(ResultCode::Ok, "PBSZ=0").await,
 
-            Command::Pwd => {
-                let msg = format!("{}", self.cwd.to_str().unwrap_or(""));
-                if !msg.is_empty() {
-                    let message = format!("\"/{}\"", msg);
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::PATHNAMECreated,
-                        &format!("\"/{}\" ", msg),
-                    );
-                } else {
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::FileNotFound,
-                        "no such file or directory",
+            #[cfg(not(feature = "secure"))]
+            Command::Pbsz => {
+                self.reply(ResultCode::CommandNotImplemented, "Not Implemented")
+                    .await
+            }
+
+            #[cfg(feature = "secure")]
+            Command::Prot(level) => match level {
+                'P' => {
+                    self.protected = true;
+                    self.reply(ResultCode::Ok, "protection level set to private")
+                        .await;
+                }
+                'C' => {
+                    self.protected = false;
+                    self.reply(ResultCode::Ok, "protection level set to clear")
+                        .await;
+                }
+                _ => {
+                    self.reply(
+                        ResultCode::CommandNotImplementedForThatParameter,
+                        "unsupported protection level",
                     )
+                    .await
                 }
+            },
+
+            #[cfg(not(feature = "secure"))]
+            Command::Prot(_) => {
+                self.reply(ResultCode::CommandNotImplemented, "Not Implemented")
+                    .await
             }
 
-            Command::Type => send_cmd(
-                &mut self.stream,
-                ResultCode::Ok,
-                "Transfer type changed successfully",
-            ),
+            Command::NoOp => self.reply(ResultCode::Ok, "Doing nothing...").await,
+
+            Command::Syst => self.reply(ResultCode::Ok, "I won't tell").await,
+
+            Command::Pwd => {
+                if !self.require_login().await {
+                    return;
+                }
+                let msg = self.virtual_path(&self.cwd);
+                self.reply(ResultCode::PATHNAMECreated, &format!("\"{}\" ", msg))
+                    .await;
+            }
+
+            Command::Type => {
+                self.reply(ResultCode::Ok, "Transfer type changed successfully")
+                    .await
+            }
+
+            Command::Cwd(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(&path) {
+                    Some(resolved) if resolved.is_dir() => {
+                        self.cwd = resolved;
+                        self.reply(ResultCode::RequestedFileActionOkay, "directory changed successfully")
+                            .await
+                    }
+                    _ => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::CdUp => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(std::path::Path::new("..")) {
+                    Some(resolved) => {
+                        self.cwd = resolved;
+                        self.reply(ResultCode::RequestedFileActionOkay, "directory changed successfully")
+                            .await
+                    }
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Mkd(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(&path) {
+                    Some(resolved) => match std::fs::create_dir(&resolved) {
+                        Ok(()) => {
+                            self.reply(
+                                ResultCode::PATHNAMECreated,
+                                &format!("\"{}\" created", self.virtual_path(&resolved)),
+                            )
+                            .await
+                        }
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "cannot create directory")
+                                .await
+                        }
+                    },
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Rmd(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(&path) {
+                    Some(resolved) => match std::fs::remove_dir(&resolved) {
+                        Ok(()) => {
+                            self.reply(ResultCode::RequestedFileActionOkay, "directory removed")
+                                .await
+                        }
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "cannot remove directory")
+                                .await
+                        }
+                    },
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Dele(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(&path) {
+                    Some(resolved) => match std::fs::remove_file(&resolved) {
+                        Ok(()) => {
+                            self.reply(ResultCode::RequestedFileActionOkay, "file deleted")
+                                .await
+                        }
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "cannot delete file")
+                                .await
+                        }
+                    },
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Rnfr(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                match self.resolve_path(&path) {
+                    Some(resolved) => {
+                        self.rename_from = Some(resolved);
+                        self.reply(
+                            ResultCode::RequestedFileActionPendingFurtherInformation,
+                            "waiting for RNTO",
+                        )
+                        .await
+                    }
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Rnto(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                let from = match self.rename_from.take() {
+                    Some(from) => from,
+                    None => {
+                        self.reply(ResultCode::BadSequenceOfCommands, "RNFR required first")
+                            .await;
+                        return;
+                    }
+                };
+                match self.resolve_path(&path) {
+                    Some(to) => match std::fs::rename(&from, &to) {
+                        Ok(()) => {
+                            self.reply(ResultCode::RequestedFileActionOkay, "renamed successfully")
+                                .await
+                        }
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "cannot rename file")
+                                .await
+                        }
+                    },
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
             Command::Pasv => {
+                if !self.require_login().await {
+                    return;
+                }
                 if self.data_writer.is_some() {
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::DataConnectionAlreadyOpen,
-                        "already listening....",
-                    )
+                    self.reply(ResultCode::DataConnectionAlreadyOpen, "already listening....")
+                        .await
                 } else {
-                    // Jika kita sudah memiliki koneksi data dengan klien ini, kita tidak perlu membuka yang baru, jadi kita tidak perlu melakukan apa pun:
-                    let port = 43210;
-                    send_cmd(
-                        &mut self.stream,
+                    // Port ephemeral (0) dipilih OS per sesi, bukan lagi konstanta 43210 yang
+                    // dipakai bersama semua client.
+                    let listener = match TcpListener::bind(("127.0.0.1", 0)).await {
+                        Ok(listener) => listener,
+                        Err(_) => {
+                            self.reply(ResultCode::ServiceNotAvailable, "cannot open data listener")
+                                .await;
+                            return;
+                        }
+                    };
+                    let port = listener.local_addr().map(|addr| addr.port()).unwrap_or(0);
+                    self.reply(
                         ResultCode::EnteringPassiveMode,
                         &format!("127.0.0.1,{},{}", port >> 8, port & 0xFF),
-                    );
-                    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
-                    let listener = TcpListener::bind(&addr).unwrap();
-                    match listener.incoming().next() {
-                        Some(Ok(client)) => {
-                            self.data_writer = Some(client);
+                    )
+                    .await;
+
+                    match listener.accept().await {
+                        Ok((client, _addr)) => self.finish_pasv_connection(client).await,
+                        Err(_) => {
+                            self.reply(ResultCode::ServiceNotAvailable, "issue happend...")
+                                .await
                         }
-                        _ => send_cmd(
-                            &mut self.stream,
-                            ResultCode::ServiceNotAvailable,
-                            "issue happend...",
-                        ),
                     }
                 }
             }
 
+            Command::Port(ip, port) => {
+                // Tolak PORT yang menunjuk host lain selain peer control connection ini -
+                // kalau tidak, client mana pun bisa membuat server dial sembarang
+                // host/port pihak ketiga (classic FTP bounce attack).
+                if self.control_peer_ip != Some(IpAddr::V4(ip)) {
+                    self.reply(
+                        ResultCode::InvalidParameterOrArgument,
+                        "PORT address must match the control connection's peer",
+                    )
+                    .await;
+                    return;
+                }
+                self.data_writer = None;
+                self.port_addr = Some(SocketAddr::new(IpAddr::V4(ip), port));
+                self.reply(ResultCode::Ok, "PORT command successful").await;
+            }
+
             Command::List => {
-                if let Some(ref mut data_writer) = self.data_writer {
-                    let mut tmp = PathBuf::from(".");
-                    send_cmd(
-                        &mut self.stream,
+                if !self.require_login().await {
+                    return;
+                }
+                let dir = match self.resolve_path(std::path::Path::new(".")) {
+                    Some(dir) => dir,
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await;
+                        return;
+                    }
+                };
+                match self.acquire_data_connection().await {
+                Some(mut data_writer) => {
+                    self.reply(
                         ResultCode::DataConnectionAlreadyOpen,
                         "starting to list directory.....",
-                    );
+                    )
+                    .await;
 
                     let mut out = String::new();
-                    for entry in read_dir(tmp).unwrap() {
-                        for entry in dir {
-                            if let Ok(entry) = entry {
-                                add_file_info(entry.path(), &mut out);
+                    if let Ok(entries) = std::fs::read_dir(dir) {
+                        for entry in entries.flatten() {
+                            add_file_info(entry.path(), &mut out);
+                        }
+                    }
+                    send_data(&mut data_writer, &out).await;
+
+                    self.reply(ResultCode::ClosingDataConnection, "Transfer done")
+                        .await;
+                }
+                    None => {
+                        self.reply(ResultCode::ConnectionClosed, "No opened data connection")
+                            .await
+                    }
+                }
+            }
+
+            Command::Retr(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                let path = match self.resolve_path(&path) {
+                    Some(path) => path,
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await;
+                        return;
+                    }
+                };
+                match self.acquire_data_connection().await {
+                Some(mut data_writer) => {
+                    match tokio::fs::File::open(&path).await {
+                        Ok(mut file) => {
+                            self.reply(ResultCode::FileStatusOk, "starting to send file...")
+                                .await;
+                            match tokio::io::copy(&mut file, &mut data_writer).await {
+                                Ok(_) => {
+                                    self.reply(ResultCode::ClosingDataConnection, "Transfer done")
+                                        .await
+                                }
+                                Err(_) => {
+                                    self.reply(ResultCode::LocalErrorInProcessing, "error sending file")
+                                        .await
+                                }
                             }
                         }
-                        send_data(data_writer, &out);
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "no such file or directory")
+                                .await
+                        }
                     }
-                } else {
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::ConnectionClosed,
-                        "No opened data connection",
-                    );
                 }
-                if self.data_writer.is_some() {
-                    self.data_writer = None;
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::ClosingDataConnection,
-                        "Transfer done",
-                    );
+                    None => {
+                        self.reply(ResultCode::CantOpenDataConnection, "No opened data connection")
+                            .await
+                    }
+                }
+            }
+
+            Command::Stor(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                let path = match self.resolve_path(&path) {
+                    Some(path) => path,
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await;
+                        return;
+                    }
+                };
+                match self.acquire_data_connection().await {
+                Some(mut data_writer) => {
+                    match tokio::fs::File::create(&path).await {
+                        Ok(mut file) => {
+                            self.reply(ResultCode::FileStatusOk, "starting to receive file...")
+                                .await;
+                            match tokio::io::copy(&mut data_writer, &mut file).await {
+                                Ok(_) => {
+                                    self.reply(ResultCode::ClosingDataConnection, "Transfer done")
+                                        .await
+                                }
+                                Err(_) => {
+                                    self.reply(
+                                        ResultCode::LocalErrorInProcessing,
+                                        "error receiving file",
+                                    )
+                                    .await
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            self.reply(ResultCode::FileNotFound, "cannot create file")
+                                .await
+                        }
+                    }
+                }
+                    None => {
+                        self.reply(ResultCode::CantOpenDataConnection, "No opened data connection")
+                            .await
+                    }
+                }
+            }
+
+            Command::Size(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                let path = match self.resolve_path(&path) {
+                    Some(path) => path,
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await;
+                        return;
+                    }
+                };
+                match tokio::fs::metadata(&path).await {
+                    Ok(meta) => {
+                        self.reply(ResultCode::FileStatus, &format!("{}", meta.len()))
+                            .await
+                    }
+                    Err(_) => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
+                }
+            }
+
+            Command::Mdtm(path) => {
+                if !self.require_login().await {
+                    return;
+                }
+                let path = match self.resolve_path(&path) {
+                    Some(path) => path,
+                    None => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await;
+                        return;
+                    }
+                };
+                match tokio::fs::metadata(&path).await {
+                    Ok(meta) => {
+                        let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+                        self.reply(ResultCode::FileStatus, &format_mdtm(modified))
+                            .await
+                    }
+                    Err(_) => {
+                        self.reply(ResultCode::FileNotFound, "no such file or directory")
+                            .await
+                    }
                 }
             }
 
             Command::User(username) => {
                 if username.is_empty() {
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::InvalidParameterOrArgument,
-                        "invalid username",
-                    )
+                    self.reply(ResultCode::InvalidParameterOrArgument, "invalid username")
+                        .await
                 } else {
-                    self.name = Some(username.to_owned());
-                    send_cmd(
-                        &mut self.stream,
-                        ResultCode::UserLoggedIn,
-                        &format!("welcome {}!", username),
+                    self.login = LoginState::WaitingForPassword(username.clone());
+                    self.reply(
+                        ResultCode::UserNameOkayNeedPassword,
+                        &format!("username ok for {}, need password", username),
                     )
+                    .await
                 }
             }
-            Command::Unknown(s) => send_cmd(
-                &mut self.stream,
-                ResultCode::UnknownCommand,
-                &format!("command {} not Implemented", s),
-            ),
+
+            Command::Pass(password) => match self.login.clone() {
+                LoginState::WaitingForPassword(username) => {
+                    let ok = match self.credentials.get(&username) {
+                        Some((salt, expected)) => &hash_password(&password, salt) == expected,
+                        None => false,
+                    };
+                    if ok {
+                        self.login = LoginState::LoggedIn(username.clone());
+                        self.reply(ResultCode::UserLoggedIn, &format!("welcome {}!", username))
+                            .await;
+                    } else {
+                        self.login = LoginState::Anonymous;
+                        self.reply(ResultCode::NotLoggedIn, "invalid username or password")
+                            .await;
+                    }
+                }
+                _ => {
+                    self.reply(ResultCode::BadSequenceOfCommands, "send USER first")
+                        .await
+                }
+            },
+
+            Command::Unknown(s) => {
+                self.reply(
+                    ResultCode::UnknownCommand,
+                    &format!("command {} not Implemented", s),
+                )
+                .await
+            }
         }
     }
 }
 
-fn send_data(stream: &mut TcpStream, s: &str) {}
+async fn send_data<W: AsyncWrite + Unpin>(stream: &mut W, s: &str) {
+    if let Err(e) = stream.write_all(s.as_bytes()).await {
+        println!("error sending data: {}", e);
+    }
+}
 
-fn add_file_info(path: PathBuf, out: &mut str) {
-    let extra = if path.is_dir() { "/" } else { "" };
+// Menulis satu baris gaya `ls -l` ke `out`. Links/owner/group di-hardcode karena
+// server ini tidak melacak kepemilikan file sesungguhnya.
+fn add_file_info(path: PathBuf, out: &mut String) {
     let is_dir = if path.is_dir() { "d" } else { "-" };
 
     let meta = match ::std::fs::metadata(&path) {
@@ -298,59 +1079,177 @@ fn add_file_info(path: PathBuf, out: &mut str) {
         _ => return,
     };
 
-    let (time, file_size) = get_file_info(&meta);
-    let path = match path.to_str() {
-        Some(path) => match path.split("/").last() {
-            Some(path) => path,
-            _ => return,
-        },
+    let (mtime, file_size) = get_file_info(&meta);
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
         _ => return,
     };
 
-    let right = if meta.permissions().readonly() {
+    let rights = if meta.permissions().readonly() {
         "r--r--r--"
     } else {
         "rw-rw-rw-"
     };
+
+    out.push_str(&format!(
+        "{}{} 1 owner group {:>10} {} {}\r\n",
+        is_dir, rights, file_size, mtime, name
+    ));
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Algoritma Howard Hinnant untuk mengubah "hari sejak epoch" jadi (tahun, bulan, tanggal)
+// tanpa perlu crate kalender: http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = if month <= 2 { y + 1 } else { y };
+    (year, month, day)
+}
+
+fn decompose_mtime(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time_of_day = secs % 86400;
+    let hour = (time_of_day / 3600) as u32;
+    let minute = (time_of_day % 3600 / 60) as u32;
+    let second = (time_of_day % 60) as u32;
+    (year, month, day, hour, minute, second)
+}
+
+// Format `ls -l`: "Mon DD HH:MM", dipakai di LIST.
+fn format_ls_mtime(time: SystemTime) -> String {
+    let (_, month, day, hour, minute, _) = decompose_mtime(time);
+    format!("{} {:>2} {:02}:{:02}", MONTH_NAMES[(month - 1) as usize], day, hour, minute)
+}
+
+// Format MDTM: "YYYYMMDDHHMMSS", sesuai yang diharapkan MDTM_RE pada crate client `ftp`.
+fn format_mdtm(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = decompose_mtime(time);
+    format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, hour, minute, second)
+}
+
+fn get_file_info(meta: &Metadata) -> (String, u64) {
+    let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+    (format_ls_mtime(modified), meta.len())
+}
+
+// Direktori root yang menjadi batas sandbox untuk semua client; bisa diatur lewat
+// env var FTP_ROOT, default ke current dir kalau tidak diset.
+fn server_root() -> PathBuf {
+    std::env::var("FTP_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+}
+
+#[cfg(feature = "secure")]
+type MaybeTlsAcceptor = Option<Arc<TlsAcceptor>>;
+#[cfg(not(feature = "secure"))]
+type MaybeTlsAcceptor = ();
+
+// Memuat sertifikat PKCS#12 dari FTPS_CERT (dilindungi kata sandi FTPS_CERT_PASSWORD)
+// dan membangun TlsAcceptor untuk AUTH TLS. Kalau FTPS_CERT tidak diset, server tetap
+// jalan tapi AUTH TLS akan membalas "TLS is not configured on this server".
+#[cfg(feature = "secure")]
+fn load_tls_acceptor() -> MaybeTlsAcceptor {
+    let cert_path = std::env::var("FTPS_CERT").ok()?;
+    let password = std::env::var("FTPS_CERT_PASSWORD").unwrap_or_default();
+    let identity_bytes = std::fs::read(cert_path).ok()?;
+    let identity = native_tls::Identity::from_pkcs12(&identity_bytes, &password).ok()?;
+    let acceptor = native_tls::TlsAcceptor::new(identity).ok()?;
+    Some(Arc::new(TlsAcceptor::from(acceptor)))
 }
 
-fn get_file_info(meta: &Metadata) {}
-// Sekarang saatnya memperbarui fungsi handle_client:
-fn handle_client(mut stream: TcpStream) {
+// Satu task per client: masing-masing punya Client sendiri (cwd, login state, data connection),
+// jadi server bisa melayani banyak sesi sekaligus alih-alih terkunci pada satu koneksi.
+async fn handle_client(
+    stream: TcpStream,
+    credentials: Arc<HashMap<String, (String, String)>>,
+    root: PathBuf,
+    tls_acceptor: MaybeTlsAcceptor,
+) {
     println!("new client connected!!");
-    send_cmd(
-        &mut stream,
-        ResultCode::ServiceReadyForNewUser,
-        "Welcome to this Rust FTP",
-    );
+    let _ = &tls_acceptor; // dipakai di bawah lewat cfg; hindari warning pada build tanpa `secure`
+    #[cfg(feature = "secure")]
+    let mut client = match tls_acceptor {
+        Some(acceptor) => Client::with_tls_acceptor(stream, credentials, root, acceptor),
+        None => Client::new(stream, credentials, root),
+    };
+    #[cfg(not(feature = "secure"))]
+    let mut client = Client::new(stream, credentials, root);
+    client
+        .reply(ResultCode::ServiceReadyForNewUser, "Welcome to this Rust FTP")
+        .await;
 
-    // let client = Client::new
+    loop {
+        match client.read_command().await {
+            Ok(Some(line)) if !line.is_empty() => match Command::new(line) {
+                Ok(cmd) => client.handle_cmd(cmd).await,
+                Err(e) => println!("error parsing command: {}", e),
+            },
+            Ok(Some(_)) => continue, // baris kosong, abaikan
+            Ok(None) => {
+                println!("client disconnected");
+                break;
+            }
+            Err(e) => {
+                println!("error reading from client: {}", e);
+                break;
+            }
+        }
+    }
 }
 
-fn send_cmd(stream: &mut TcpStream, code: ResultCode, message: &str) {
+async fn send_cmd<W: AsyncWrite + Unpin>(stream: &mut W, code: ResultCode, message: &str) {
     let msg = if message.is_empty() {
         format!("{}\r\n", code as u32)
     } else {
         format!("{} {}\r\n", code as u32, message)
     };
     println!("<========= {}", msg);
-    write!(stream, "{}", msg).unwrap();
+    if let Err(e) = stream.write_all(msg.as_bytes()).await {
+        println!("error sending reply: {}", e);
+    }
 }
 
-fn main() {
-    let listner = TcpListener::bind("0.0.0.0:1234").expect("Couldn't bind this address");
+#[tokio::main]
+async fn main() {
+    let listener = TcpListener::bind("0.0.0.0:1234")
+        .await
+        .expect("Couldn't bind this address");
+
+    let credentials = Arc::new(load_credentials());
+    let root = server_root();
+    #[cfg(feature = "secure")]
+    let tls_acceptor: MaybeTlsAcceptor = load_tls_acceptor();
+    #[cfg(not(feature = "secure"))]
+    let tls_acceptor: MaybeTlsAcceptor = ();
 
     println!("Waiting for clients to connect....");
 
-    for stream in listner.incoming() {
-        match stream {
-            Ok(mut stream) => {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _addr)) => {
                 println!("New client!");
-                if let Err(_) = stream.write(b"hello") {
-                    println!("Failed to send hello... :'(");
-                }
+                let credentials = credentials.clone();
+                let root = root.clone();
+                #[cfg(feature = "secure")]
+                let tls_acceptor = tls_acceptor.clone();
+                tokio::spawn(async move {
+                    handle_client(stream, credentials, root, tls_acceptor).await;
+                });
             }
-            _ => {
+            Err(_) => {
                 println!("A client tried to connect");
             }
         }